@@ -0,0 +1,168 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use termion::color;
+
+use crate::{tokenize, parse_statement, FunMap, ResultDyn, TokenKind};
+
+/// Backs the interactive session: validates incomplete input, highlights
+/// known functions/variables, and completes their names.
+struct ReplHelper<'a> {
+    functions: &'a FunMap<'a>,
+    variables: Rc<RefCell<HashMap<String, f64>>>
+}
+
+impl<'a> Helper for ReplHelper<'a> {}
+
+impl<'a> Validator for ReplHelper<'a> {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        // Mirrors the scope counting in `advance_to_matching_paren`: an unmatched
+        // opening parenthesis means the user isn't done typing yet.
+        let Ok(tokens) = tokenize(ctx.input()) else {
+            return Ok(ValidationResult::Valid(None));
+        };
+
+        let scope = tokens.iter().fold(0_i32, |scope, t| match t.kind {
+            TokenKind::LeftParen => scope + 1,
+            TokenKind::RightParen => scope - 1,
+            _ => scope
+        });
+
+        if scope > 0 {
+            Ok(ValidationResult::Incomplete)
+        }
+        else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl<'a> Completer for ReplHelper<'a> {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos].rfind(|c: char| !c.is_ascii_alphanumeric()).map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+
+        if word.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+
+        let variables = self.variables.borrow();
+
+        let candidates = self.functions.keys().map(|name| name.to_string())
+            .chain(variables.keys().cloned())
+            .filter(|name| name.starts_with(word))
+            .map(|name| Pair { display: name.clone(), replacement: name })
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+impl<'a> Hinter for ReplHelper<'a> {
+    type Hint = String;
+
+    fn hint(&self, _line: &str, _pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        None
+    }
+}
+
+impl<'a> Highlighter for ReplHelper<'a> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut out = String::new();
+        let mut chars = line.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            if c.is_ascii_digit() {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, n)) = chars.peek() {
+                    if n.is_ascii_digit() || n == '.' { chars.next(); end = j + n.len_utf8(); } else { break; }
+                }
+                out.push_str(&format!("{}{}{}", color::Fg(color::Cyan), &line[i..end], color::Fg(color::Reset)));
+            }
+            else if c.is_ascii_alphabetic() {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, n)) = chars.peek() {
+                    if n.is_ascii_alphanumeric() { chars.next(); end = j + n.len_utf8(); } else { break; }
+                }
+
+                let word = &line[i..end];
+
+                if self.functions.contains_key(word) {
+                    out.push_str(&format!("{}{}{}", color::Fg(color::Yellow), word, color::Fg(color::Reset)));
+                }
+                else if self.variables.borrow().contains_key(word) {
+                    out.push_str(&format!("{}{}{}", color::Fg(color::Green), word, color::Fg(color::Reset)));
+                }
+                else {
+                    out.push_str(word);
+                }
+            }
+            else if "+-*/^=".contains(c) {
+                out.push_str(&format!("{}{}{}", color::Fg(color::Magenta), c, color::Fg(color::Reset)));
+            }
+            else {
+                out.push(c);
+            }
+        }
+
+        Cow::Owned(out)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+/// Runs an interactive calculator session: reads expressions, evaluates them
+/// against a persistent variable map, and prints each result.
+pub fn run(functions: &FunMap) -> ResultDyn<()> {
+    let variables = Rc::new(RefCell::new(HashMap::new()));
+
+    let helper = ReplHelper { functions, variables: Rc::clone(&variables) };
+
+    let mut rl: Editor<ReplHelper, rustyline::history::DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(helper));
+
+    loop {
+        match rl.readline(">> ") {
+            Ok(line) => {
+                let line = line.trim();
+
+                if line.is_empty() {
+                    continue;
+                }
+
+                rl.add_history_entry(line)?;
+
+                let tokens = match tokenize(line) {
+                    Ok(tokens) => tokens,
+                    Err(e) => { println!("{}", e); continue; }
+                };
+
+                let statement = match parse_statement(&tokens, functions) {
+                    Ok(statement) => statement,
+                    Err(e) => { println!("{}", e.display_with_source(line)); continue; }
+                };
+
+                match statement.calc(&mut variables.borrow_mut()) {
+                    Ok(value) => println!("{} = {}", line, value),
+                    Err(e) => println!("{}", e.display_with_source(line))
+                }
+            },
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => { println!("Error: {}", e); break; }
+        }
+    }
+
+    Ok(())
+}
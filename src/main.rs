@@ -12,6 +12,8 @@ use lerp::Lerp;
 mod inspect_err;
 use inspect_err::InspectErr;
 
+mod cli;
+
 type ResultDyn<T> = std::result::Result<T, Box<dyn error::Error>>;
 
 #[derive(Debug, Clone, Copy)]
@@ -19,7 +21,14 @@ enum OpType {
     Plus,
     Sub,
     Mul,
-    Div
+    Div,
+    Pow
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Associativity {
+    Left,
+    Right
 }
 
 impl OpType {
@@ -28,10 +37,19 @@ impl OpType {
         use OpType::*;
 
         match *self {
-            Plus => 1,
-            Sub => 1,
-            Mul => 2,
-            Div => 2
+            Plus => 10,
+            Sub => 10,
+            Mul => 20,
+            Div => 20,
+            Pow => 30
+        }
+    }
+
+    /// Return the side on which equal-precedence runs of this operator group together
+    fn associativity(&self) -> Associativity {
+        match *self {
+            OpType::Pow => Associativity::Right,
+            _ => Associativity::Left
         }
     }
 }
@@ -43,18 +61,28 @@ struct Op {
     rhs: Box<Expr>
 }
 
-type FunType = fn(f64) -> f64;
-type FunMap<'a> = HashMap<&'a str, FunType>;
+type NaryFn = fn(&[f64]) -> f64;
+type FunMap<'a> = HashMap<&'a str, Callable>;
+
+/// A registered function: the underlying implementation plus the number of
+/// arguments it expects, so calls can be arity-checked.
+#[derive(Debug, Clone, Copy)]
+struct Callable {
+    fun: NaryFn,
+    arity: usize
+}
 
 struct Function {
-    arg: Box<Expr>,
-    fun: FunType
+    name: String,
+    args: Vec<Expr>,
+    callable: Callable
 }
 
 impl std::fmt::Debug for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Function")
-            .field("arg", &self.arg)
+            .field("name", &self.name)
+            .field("args", &self.args)
             .finish()
     }
 }
@@ -67,11 +95,24 @@ impl Op {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum UnaryOp {
+    Neg,
+    Pos
+}
+
+#[derive(Debug)]
+struct Unary {
+    op: UnaryOp,
+    operand: Box<Expr>
+}
+
 #[derive(Debug)]
 enum Expr {
     Value(f64),
     Variable(String),
     Operation(Op),
+    Unary(Unary),
     Function(Function)
 }
 
@@ -97,23 +138,61 @@ impl Expr {
                     Plus => lhs + rhs,
                     Sub => lhs - rhs,
                     Mul => lhs * rhs,
-                    Div => lhs / rhs
+                    Div => lhs / rhs,
+                    Pow => lhs.powf(rhs)
+                })
+            },
+            Expr::Unary(u) => {
+                let operand = u.operand.calc(variables)?;
+
+                Ok(match u.op {
+                    UnaryOp::Neg => -operand,
+                    UnaryOp::Pos => operand
                 })
             },
             Expr::Function(fun) => {
-                Ok((fun.fun)( fun.arg.calc(variables)? )) 
+                if fun.args.len() != fun.callable.arity {
+                    return Err(InvalidExpressionError::ArityMismatch {
+                        name: fun.name.clone(),
+                        expected: fun.callable.arity,
+                        found: fun.args.len()
+                    });
+                }
+
+                let args = fun.args.iter()
+                    .map(|arg| arg.calc(variables))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok((fun.callable.fun)(&args))
             }
         }
     }
 }
 
 #[derive(Debug, Clone)]
-enum Token {
+enum TokenKind {
     Number(f64),
     Op(OpType),
     Identifier(String),
     LeftParen,
-    RightParen
+    RightParen,
+    Comma,
+    Equals
+}
+
+/// A lexed token together with the byte range it occupies in the original
+/// expression, so errors can point back at the offending source text.
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize
+}
+
+impl Token {
+    fn new(kind: TokenKind, start: usize, end: usize) -> Self {
+        Token { kind, start, end }
+    }
 }
 
 fn tokenize(str: &str) -> ResultDyn<Vec<Token>> {
@@ -121,26 +200,28 @@ fn tokenize(str: &str) -> ResultDyn<Vec<Token>> {
 
     let mut tokens = Vec::new();
 
-    let mut chars = str.chars().peekable();
+    let mut chars = str.char_indices().peekable();
 
-    while let Some(c) = chars.next() {
+    while let Some((start, c)) = chars.next() {
         if c.is_digit(10) {
             let mut radix: u32 = 10;
 
             let mut sum: f64;
-            
+
+            let mut end = start + c.len_utf8();
+
             // Use prefix to indicate base
             if c == '0' {
                 match chars.peek() {
-                    Some('x' | 'X') => radix = 16,
-                    Some('o' | 'O') => radix = 8,
-                    Some('b' | 'B') => radix = 2,
+                    Some(&(_, 'x' | 'X')) => radix = 16,
+                    Some(&(_, 'o' | 'O')) => radix = 8,
+                    Some(&(_, 'b' | 'B')) => radix = 2,
                     _ => ()
                 }
 
                 if radix != 10 {
                     // chars.advance_by(1) Nightly feature
-                    chars.next().unwrap_or_default();
+                    if let Some((j, c)) = chars.next() { end = j + c.len_utf8(); }
                 }
 
                 sum = 0.0;
@@ -148,50 +229,61 @@ fn tokenize(str: &str) -> ResultDyn<Vec<Token>> {
             else {
                 sum = c.to_digit(radix).unwrap().try_into()?;
             }
-            
-            while let Some(n) = chars.peek() {
+
+            while let Some(&(_, n)) = chars.peek() {
                 if n.is_digit(radix) {
-                    let n = chars.next().unwrap();
+                    let (j, n) = chars.next().unwrap();
 
                     sum *= f64::from(radix);
                     sum += f64::from(n.to_digit(radix).unwrap());
+
+                    end = j + n.len_utf8();
                 }
                 else {
                     break;
                 }
             }
 
-            tokens.push(Token::Number(sum));
+            tokens.push(Token::new(TokenKind::Number(sum), start, end));
         }
         else {
             use OpType::*;
 
+            let end = start + c.len_utf8();
+
             match c {
-                '+' => tokens.push( Token::Op( Plus ) ),
-                '-' => tokens.push( Token::Op( Sub ) ),
-                '*' => tokens.push( Token::Op( Mul ) ),
-                '/' => tokens.push( Token::Op( Div ) ),
-                '(' => tokens.push( Token::LeftParen ),
-                ')' => tokens.push( Token::RightParen ),
+                '+' => tokens.push( Token::new(TokenKind::Op( Plus ), start, end) ),
+                '-' => tokens.push( Token::new(TokenKind::Op( Sub ), start, end) ),
+                '*' => tokens.push( Token::new(TokenKind::Op( Mul ), start, end) ),
+                '/' => tokens.push( Token::new(TokenKind::Op( Div ), start, end) ),
+                '^' => tokens.push( Token::new(TokenKind::Op( Pow ), start, end) ),
+                '(' => tokens.push( Token::new(TokenKind::LeftParen, start, end) ),
+                ')' => tokens.push( Token::new(TokenKind::RightParen, start, end) ),
+                ',' => tokens.push( Token::new(TokenKind::Comma, start, end) ),
+                '=' => tokens.push( Token::new(TokenKind::Equals, start, end) ),
                 c if c.is_ascii_alphabetic() => {
                     let mut id = String::new();
                     id.push(c);
 
-                    while let Some(c) = chars.peek() {
-                        if c.is_ascii_alphabetic() {
-                            id.push( chars.next().unwrap() );
+                    let mut end = end;
+
+                    while let Some(&(j, c)) = chars.peek() {
+                        if c.is_ascii_alphanumeric() {
+                            chars.next();
+                            id.push(c);
+                            end = j + c.len_utf8();
                         }
                         else {
                             break;
                         }
                     }
 
-                    tokens.push( Token::Identifier(id) );
+                    tokens.push( Token::new(TokenKind::Identifier(id), start, end) );
                 },
                 _ => ()
             }
         }
-        
+
     }
 
     Ok(tokens)
@@ -214,12 +306,56 @@ enum InvalidExpressionError {
         end: Option<Token>
     },
     #[error("Couldn't find a valid operation in tokens {0:?}")]
-    AmbiguousOperation(Vec<Token>)
+    AmbiguousOperation(Vec<Token>),
+    #[error("Function `{name}` expects {expected} argument(s), found {found}")]
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize
+    },
+    #[error("Can't assign to `{0}`, a function with that name already exists")]
+    ReservedFunctionName(String)
 }
 
-trait AdvanceToMatchingParen 
+impl InvalidExpressionError {
+    /// Returns the byte range in the original expression this error points at, if any.
+    fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            InvalidExpressionError::InvalidToken { found: Some(t), .. } => Some((t.start, t.end)),
+            InvalidExpressionError::NoMatchingToken { start, end } => {
+                let t = end.as_ref().unwrap_or(start);
+                Some((t.start, t.end))
+            },
+            InvalidExpressionError::AmbiguousOperation(tokens) => {
+                Some((tokens.first()?.start, tokens.last()?.end))
+            },
+            _ => None
+        }
+    }
+
+    /// Renders this error together with a caret underline pointing at its span
+    /// in the original expression, e.g.
+    ///
+    /// ```text
+    /// 2 + * 3
+    ///     ^
+    /// ```
+    fn display_with_source(&self, source: &str) -> String {
+        match self.span() {
+            Some((start, end)) => {
+                let end = end.max(start + 1);
+                let underline = format!("{}{}", " ".repeat(start), "^".repeat(end - start));
+
+                format!("{}\n{}\n{}", source, underline, self)
+            },
+            None => self.to_string()
+        }
+    }
+}
+
+trait AdvanceToMatchingParen
 where Self: std::marker::Sized {
-    fn advance_to_matching_paren(self) -> Result<Self, InvalidExpressionError>;
+    fn advance_to_matching_paren(self, open: &Token) -> Result<Self, InvalidExpressionError>;
 }
 
 macro_rules! advanceable_to_matching_paren {
@@ -227,15 +363,15 @@ macro_rules! advanceable_to_matching_paren {
         $(
             impl AdvanceToMatchingParen for $ty {
                 /// Constructs an iterator that returns the matching parenthesise
-                fn advance_to_matching_paren(mut self) -> Result<Self, InvalidExpressionError> {
+                fn advance_to_matching_paren(mut self, open: &Token) -> Result<Self, InvalidExpressionError> {
                     let mut scope = 0;
 
                     let mut last_token: Option<&Token> = None;
 
                     for t in &mut self {
-                        match t {
-                            Token::LeftParen => scope += 1,
-                            Token::RightParen => scope -= 1,
+                        match t.kind {
+                            TokenKind::LeftParen => scope += 1,
+                            TokenKind::RightParen => scope -= 1,
                             _ => ()
                         }
 
@@ -245,7 +381,7 @@ macro_rules! advanceable_to_matching_paren {
                     }
 
                     if scope >= 0 {
-                        Err(InvalidExpressionError::NoMatchingToken { start: Token::LeftParen, end: last_token.cloned() })
+                        Err(InvalidExpressionError::NoMatchingToken { start: open.clone(), end: last_token.cloned() })
                     }
                     else {
                         Ok(self)
@@ -259,15 +395,15 @@ macro_rules! advanceable_to_matching_paren {
 advanceable_to_matching_paren!(std::slice::Iter<'_, Token>, std::iter::Skip<std::slice::Iter<'_, Token>>);
 
 impl AdvanceToMatchingParen for std::iter::Enumerate<std::slice::Iter<'_, Token>> {
-    fn advance_to_matching_paren(mut self) -> Result<Self, InvalidExpressionError> {
+    fn advance_to_matching_paren(mut self, open: &Token) -> Result<Self, InvalidExpressionError> {
         let mut scope = 0;
 
         let mut last_token: Option<&Token> = None;
 
         for (_, t) in &mut self {
-            match t {
-                Token::LeftParen => scope += 1,
-                Token::RightParen => scope -= 1,
+            match t.kind {
+                TokenKind::LeftParen => scope += 1,
+                TokenKind::RightParen => scope -= 1,
                 _ => ()
             }
 
@@ -277,7 +413,7 @@ impl AdvanceToMatchingParen for std::iter::Enumerate<std::slice::Iter<'_, Token>
         }
 
         if scope >= 0 {
-            Err(InvalidExpressionError::NoMatchingToken { start: Token::LeftParen, end: last_token.cloned() })
+            Err(InvalidExpressionError::NoMatchingToken { start: open.clone(), end: last_token.cloned() })
         }
         else {
             Ok(self)
@@ -285,13 +421,53 @@ impl AdvanceToMatchingParen for std::iter::Enumerate<std::slice::Iter<'_, Token>
     }
 }
 
+/// A `+`/`-` is a unary prefix rather than a binary operator when it opens the
+/// slice or immediately follows another operator or an opening parenthesis.
+fn is_unary_position(tokens: &[Token], idx: usize) -> bool {
+    matches!(idx.checked_sub(1).map(|i| &tokens[i].kind), None | Some(TokenKind::Op(_) | TokenKind::LeftParen))
+}
+
+/// Precedence given to a *leading* unary `+`/`-` when it competes with the rest of the
+/// expression for the lowest-precedence split. It sits between `*`/`/` and `^` so that
+/// `-2^2` parses as `-(2^2)` (unary binds looser than `^`) while `3*-2` still splits at
+/// `*` (unary binds tighter than `*`/`/`).
+const UNARY_PRECEDENCE: i32 = 25;
+
+/// Splits a token slice on its top-level commas, skipping over any nested
+/// inside parenthesises (used to collect function call arguments).
+fn split_top_level_commas(tokens: &[Token]) -> Result<Vec<&[Token]>, InvalidExpressionError> {
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    let mut it = tokens.iter().enumerate();
+
+    while let Some((i, t)) = it.next() {
+        match &t.kind {
+            TokenKind::LeftParen => it = it.advance_to_matching_paren(t)?,
+            TokenKind::Comma => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            },
+            _ => ()
+        }
+    }
+
+    parts.push(&tokens[start..]);
+
+    Ok(parts)
+}
+
 fn parse(tokens: &[Token], functions: &FunMap) -> Result<Expr, InvalidExpressionError> {
     if tokens.len() == 1 {
-        match tokens.first() {
-            Some(Token::Number(num)) => Ok(Expr::Value(*num)),
-            Some(Token::Identifier(v)) => Ok(Expr::Variable(v.clone())),
-            _ => Err(InvalidExpressionError::InvalidToken { 
-                expected: "Token::Number | Token::Identifier", 
+        match tokens.first().map(|t| &t.kind) {
+            Some(TokenKind::Number(num)) => Ok(Expr::Value(*num)),
+            Some(TokenKind::Identifier(v)) => Ok(Expr::Variable(v.clone())),
+            _ => Err(InvalidExpressionError::InvalidToken {
+                expected: "Token::Number | Token::Identifier",
                 found: tokens.first().cloned()
             })
         }
@@ -299,10 +475,10 @@ fn parse(tokens: &[Token], functions: &FunMap) -> Result<Expr, InvalidExpression
     else {
         // If slice is wrapped in parenthesises, remove them
         // First check if it starts with one
-        if let Some(Token::LeftParen) = tokens.first() {
+        if let Some(open @ Token { kind: TokenKind::LeftParen, .. }) = tokens.first() {
 
             // Then check if the matching parenthesis is at the end of the slice
-            if tokens.iter().skip(1).advance_to_matching_paren()?.next().is_none() {
+            if tokens.iter().skip(1).advance_to_matching_paren(open)?.next().is_none() {
                 return parse( &tokens[1..tokens.len()-1], functions );
             }
         }
@@ -313,15 +489,31 @@ fn parse(tokens: &[Token], functions: &FunMap) -> Result<Expr, InvalidExpression
         let mut it = tokens.iter().enumerate();
 
         while let Some(t) = it.next() {
-            match t.1 {
-                Token::Op(op) => {
-                    if op.precedence() <= precedence {
+            match &t.1.kind {
+                // A *leading* unary `+`/`-` can still be the overall split point (`-2^2`
+                // should parse as `-(2^2)`), so it competes in the scan at `UNARY_PRECEDENCE`.
+                TokenKind::Op(OpType::Sub | OpType::Plus) if t.0 == 0 && UNARY_PRECEDENCE <= precedence => {
+                    idx = t.0;
+                    precedence = UNARY_PRECEDENCE;
+                },
+                // A unary anywhere else is already the operand of the operator right before
+                // it, so it's never itself a split candidate: skip it entirely.
+                TokenKind::Op(OpType::Sub | OpType::Plus) if is_unary_position(tokens, t.0) => (),
+                TokenKind::Op(op) => {
+                    // For right-associative operators (e.g. `^`), keep the *first* operator found
+                    // at the lowest precedence instead of the last, so runs group to the right.
+                    let keep = match op.associativity() {
+                        Associativity::Left => op.precedence() <= precedence,
+                        Associativity::Right => op.precedence() < precedence
+                    };
+
+                    if keep {
                         idx = t.0;
                         precedence = op.precedence();
                     }
                 },
                 // By definition, parenthesises have a higher precedence, so we'll skip them
-                Token::LeftParen => it = it.advance_to_matching_paren()?,
+                TokenKind::LeftParen => it = it.advance_to_matching_paren(t.1)?,
                 _ => ()
             }
         }
@@ -329,12 +521,18 @@ fn parse(tokens: &[Token], functions: &FunMap) -> Result<Expr, InvalidExpression
         // If no operator was found, search for functions
         if precedence == i32::MAX {
             let mut it = tokens.iter();
-            
-            if let ( Some(Token::Identifier(id)), Some(Token::LeftParen), Some(Token::RightParen) ) = ( it.next(), it.next(), it.last() ) {
-                if let Some(fun) = functions.get(id.as_str()) {
+
+            if let ( Some(Token { kind: TokenKind::Identifier(id), .. }), Some(Token { kind: TokenKind::LeftParen, .. }), Some(Token { kind: TokenKind::RightParen, .. }) ) = ( it.next(), it.next(), it.last() ) {
+                if let Some(callable) = functions.get(id.as_str()) {
+                    let args = split_top_level_commas(&tokens[2..tokens.len()-1])?
+                        .into_iter()
+                        .map(|arg_tokens| parse(arg_tokens, functions))
+                        .collect::<Result<Vec<_>, _>>()?;
+
                     Ok(Expr::Function(Function {
-                        arg: Box::new( parse( &tokens[2..tokens.len()-1], functions )? ),
-                        fun: *fun
+                        name: id.clone(),
+                        args,
+                        callable: *callable
                     }))
                 }
                 else {
@@ -345,7 +543,21 @@ fn parse(tokens: &[Token], functions: &FunMap) -> Result<Expr, InvalidExpression
                 Err(InvalidExpressionError::AmbiguousOperation(tokens.to_vec()))
             }
         }
-        else if let Token::Op(op) = tokens[idx] {
+        // A leading unary `+`/`-` won the split above: it has no left operand, so recurse
+        // into the operand on its right instead of treating it as a binary split.
+        else if idx == 0 && matches!(tokens[0].kind, TokenKind::Op(OpType::Sub | OpType::Plus)) {
+            let op = match tokens[0].kind {
+                TokenKind::Op(OpType::Sub) => UnaryOp::Neg,
+                TokenKind::Op(OpType::Plus) => UnaryOp::Pos,
+                _ => unreachable!()
+            };
+
+            Ok(Expr::Unary(Unary {
+                op,
+                operand: Box::new(parse(&tokens[1..], functions)?)
+            }))
+        }
+        else if let TokenKind::Op(op) = tokens[idx].kind {
             Ok(Expr::Operation(Op::new(
                 op,
                 Box::new(parse(&tokens[..idx], functions)?),
@@ -356,6 +568,47 @@ fn parse(tokens: &[Token], functions: &FunMap) -> Result<Expr, InvalidExpression
     }
 }
 
+/// A top-level line of input: either a bare expression, or a binding of the
+/// form `name = <expr>` that stores its result under `name`.
+#[derive(Debug)]
+enum Statement {
+    Expr(Expr),
+    Assignment {
+        name: String,
+        value: Expr
+    }
+}
+
+impl Statement {
+    fn calc(&self, variables: &mut HashMap<String, f64>) -> Result<f64, InvalidExpressionError> {
+        match self {
+            Statement::Expr(expr) => expr.calc(variables),
+            Statement::Assignment { name, value } => {
+                let result = value.calc(variables)?;
+                variables.insert(name.clone(), result);
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// Parses a full line of input, recognizing a leading `Identifier '=' <expr>`
+/// as an assignment before falling back to a plain expression.
+fn parse_statement(tokens: &[Token], functions: &FunMap) -> Result<Statement, InvalidExpressionError> {
+    if let [Token { kind: TokenKind::Identifier(name), .. }, Token { kind: TokenKind::Equals, .. }, rest @ ..] = tokens {
+        if functions.contains_key(name.as_str()) {
+            return Err(InvalidExpressionError::ReservedFunctionName(name.clone()));
+        }
+
+        return Ok(Statement::Assignment {
+            name: name.clone(),
+            value: parse(rest, functions)?
+        });
+    }
+
+    Ok(Statement::Expr(parse(tokens, functions)?))
+}
+
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -370,12 +623,16 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    Calc { expression: String },
-    Graph { 
+    Calc {
+        #[clap(allow_hyphen_values = true)]
+        expression: String
+    },
+    Graph {
         #[clap(short = 'x', default_value_t = 0.0)]
         origin: f64,
         #[clap(short = 'w', default_value_t = 1.0)]
         width: f64,
+        #[clap(allow_hyphen_values = true)]
         expression: String
     },
     Cli
@@ -386,7 +643,7 @@ fn main() -> ResultDyn<()> {
 
     macro_rules! f64_fn_tuple {
         ($func_name:ident) => {
-            (stringify!($func_name), f64::$func_name as FunType)
+            (stringify!($func_name), Callable { fun: |args| f64::$func_name(args[0]), arity: 1 })
         }
     }
 
@@ -396,18 +653,21 @@ fn main() -> ResultDyn<()> {
         f64_fn_tuple!(tan),
         f64_fn_tuple!(log2),
         f64_fn_tuple!(ln),
+        ("atan2", Callable { fun: |args| args[0].atan2(args[1]), arity: 2 }),
+        ("log", Callable { fun: |args| args[0].log(args[1]), arity: 2 }),
+        ("min", Callable { fun: |args| args[0].min(args[1]), arity: 2 }),
+        ("max", Callable { fun: |args| args[0].max(args[1]), arity: 2 }),
     ]);
 
-    let log = |e: &InvalidExpressionError|{ println!("{}", e) };
-
     match &cli.subcommand {
         Commands::Calc { expression } | Commands::Graph { expression, .. } => {
+            let log = |e: &InvalidExpressionError| { println!("{}", e.display_with_source(expression)) };
 
             let tokens = tokenize(expression)?;
 
             let parsed = parse(&tokens, &functions)
                 .my_inspect_err(log)
-                .unwrap();
+                .unwrap_or_else(|_| std::process::exit(1));
 
             if cli.verbose {
                 println!("> Input\n{}", expression);
@@ -421,7 +681,7 @@ fn main() -> ResultDyn<()> {
 
                     println!("{} = {}", expression, parsed.calc(&variables)
                         .my_inspect_err(log)
-                        .unwrap());
+                        .unwrap_or_else(|_| std::process::exit(1)));
                 },
                 &Commands::Graph { origin, width, ..} => {
                     let mut variables: HashMap<String, f64> = HashMap::from([
@@ -437,7 +697,7 @@ fn main() -> ResultDyn<()> {
 
                             parsed.calc(&variables)
                                 .my_inspect_err(log)
-                                .unwrap()
+                                .unwrap_or_else(|_| std::process::exit(1))
                         })
                         .collect();
 
@@ -540,8 +800,154 @@ fn main() -> ResultDyn<()> {
                 _ => unreachable!()
             }
         },
-        Commands::Cli => unimplemented!()
+        Commands::Cli => cli::run(&functions)?
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calc_str(expr: &str) -> f64 {
+        let tokens = tokenize(expr).unwrap();
+        let functions: FunMap = HashMap::new();
+        let parsed = parse(&tokens, &functions).unwrap();
+        parsed.calc(&HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn pow_is_right_associative() {
+        assert_eq!(calc_str("2^3^2"), 512.0);
+    }
+
+    #[test]
+    fn sub_is_left_associative() {
+        assert_eq!(calc_str("8-3-2"), 3.0);
+    }
+
+    #[test]
+    fn multi_arg_call_evaluates_all_arguments() {
+        let functions: FunMap = HashMap::from([
+            ("add2", Callable { fun: |args| args[0] + args[1], arity: 2 })
+        ]);
+
+        let tokens = tokenize("add2(1, 2)").unwrap();
+        let parsed = parse(&tokens, &functions).unwrap();
+
+        assert_eq!(parsed.calc(&HashMap::new()).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn arity_mismatch_is_rejected() {
+        let functions: FunMap = HashMap::from([
+            ("add2", Callable { fun: |args| args[0] + args[1], arity: 2 })
+        ]);
+
+        let tokens = tokenize("add2(1)").unwrap();
+        let parsed = parse(&tokens, &functions).unwrap();
+
+        let err = parsed.calc(&HashMap::new()).unwrap_err();
+        assert!(matches!(err, InvalidExpressionError::ArityMismatch { expected: 2, found: 1, .. }));
+    }
+
+    #[test]
+    fn leading_unary_minus_binds_looser_than_pow() {
+        assert_eq!(calc_str("-2^2"), -4.0);
+    }
+
+    #[test]
+    fn leading_unary_minus_binds_tighter_than_mul() {
+        assert_eq!(calc_str("3*-2"), -6.0);
+    }
+
+    #[test]
+    fn double_unary_minus_cancels() {
+        assert_eq!(calc_str("--5"), 5.0);
+    }
+
+    #[test]
+    fn unary_minus_wraps_parenthesised_expr() {
+        assert_eq!(calc_str("-(3+1)"), -4.0);
+    }
+
+    #[test]
+    fn assignment_persists_variable_for_later_statements() {
+        let functions: FunMap = HashMap::new();
+        let mut variables: HashMap<String, f64> = HashMap::new();
+
+        let tokens = tokenize("a = 3+4").unwrap();
+        let statement = parse_statement(&tokens, &functions).unwrap();
+        assert_eq!(statement.calc(&mut variables).unwrap(), 7.0);
+
+        let tokens = tokenize("a").unwrap();
+        let statement = parse_statement(&tokens, &functions).unwrap();
+        assert_eq!(statement.calc(&mut variables).unwrap(), 7.0);
+    }
+
+    #[test]
+    fn assignment_can_reassign_a_variable() {
+        let functions: FunMap = HashMap::new();
+        let mut variables: HashMap<String, f64> = HashMap::new();
+
+        let tokens = tokenize("a = 1").unwrap();
+        parse_statement(&tokens, &functions).unwrap().calc(&mut variables).unwrap();
+
+        let tokens = tokenize("a = 2").unwrap();
+        assert_eq!(parse_statement(&tokens, &functions).unwrap().calc(&mut variables).unwrap(), 2.0);
+
+        assert_eq!(*variables.get("a").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn assignment_to_a_function_name_is_rejected() {
+        let functions: FunMap = HashMap::from([
+            ("sin", Callable { fun: |args| args[0].sin(), arity: 1 })
+        ]);
+
+        let tokens = tokenize("sin = 1").unwrap();
+        let err = parse_statement(&tokens, &functions).unwrap_err();
+
+        assert!(matches!(err, InvalidExpressionError::ReservedFunctionName(name) if name == "sin"));
+    }
+
+    #[test]
+    fn display_with_source_underlines_no_matching_token_span() {
+        let source = "(2+3";
+        let functions: FunMap = HashMap::new();
+
+        let tokens = tokenize(source).unwrap();
+        let err = parse(&tokens, &functions).unwrap_err();
+        assert!(matches!(err, InvalidExpressionError::NoMatchingToken { .. }));
+
+        // The last token scanned while looking for the closing `)` is the final `3`,
+        // so the caret should land under it.
+        assert_eq!(err.display_with_source(source), format!("{}\n{}\n{}", source, "   ^", err));
+    }
+
+    #[test]
+    fn display_with_source_underlines_ambiguous_operation_span() {
+        let source = "2 3";
+        let functions: FunMap = HashMap::new();
+
+        let tokens = tokenize(source).unwrap();
+        let err = parse(&tokens, &functions).unwrap_err();
+        assert!(matches!(err, InvalidExpressionError::AmbiguousOperation(_)));
+
+        // The span covers every token in the ambiguous run, i.e. the whole source here.
+        assert_eq!(err.display_with_source(source), format!("{}\n{}\n{}", source, "^^^", err));
+    }
+
+    #[test]
+    fn display_with_source_underlines_invalid_token_span() {
+        let source = "^";
+        let functions: FunMap = HashMap::new();
+
+        let tokens = tokenize(source).unwrap();
+        let err = parse(&tokens, &functions).unwrap_err();
+        assert!(matches!(err, InvalidExpressionError::InvalidToken { .. }));
+
+        assert_eq!(err.display_with_source(source), format!("{}\n{}\n{}", source, "^", err));
+    }
+}